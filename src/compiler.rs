@@ -0,0 +1,157 @@
+use crate::{CustomCommand, Instruction, OpCode, Value, MEM_SIZE};
+
+// Lowers a flattened instruction stream to x86_64 NASM assembly text. The data
+// stack is modeled directly on the machine stack (push/pop rax/rbx/...), word
+// definitions become their own `call`-able labels, and control flow becomes
+// plain `jmp`/`jz` against per-block labels. `words` must be the same
+// CustomCommand table codegen resolved OpCode::Call indices against.
+// A word moves the return address pushed by `call` off the machine stack onto a
+// separate return stack (`ret_stack`, addressed through r15) on entry and puts it
+// back just before `ret`, so its body sees the caller's data on top. The return
+// stack holds 1024 nested calls; deeper nesting is not checked.
+pub fn compile_to_nasm(instructions: &[Instruction], words: &[CustomCommand]) -> String {
+    let mut strings: Vec<(String, String)> = vec![];
+
+    let mut text = String::new();
+    text.push_str("_start:\n");
+    text.push_str("    mov r15, ret_stack\n");
+    text.push_str(&compile_block(instructions, "L", words, &mut strings));
+    text.push_str("    mov rax, 60\n");
+    text.push_str("    xor rdi, rdi\n");
+    text.push_str("    syscall\n");
+
+    for (idx, word) in words.iter().enumerate() {
+        text.push_str(&format!("func_{}_{}:\n", idx, sanitize(&word.name)));
+        text.push_str("    pop rax\n    mov [r15], rax\n    add r15, 8\n");
+        let prefix = format!("W{}", idx);
+        text.push_str(&compile_block(&word.instructions, &prefix, words, &mut strings));
+        text.push_str("    sub r15, 8\n    push qword [r15]\n");
+        text.push_str("    ret\n");
+    }
+
+    let mut out = String::new();
+    out.push_str("section .bss\n");
+    out.push_str(&format!("    mem: resb {}\n", MEM_SIZE));
+    out.push_str("    ret_stack: resq 1024\n");
+    out.push('\n');
+    if !strings.is_empty() {
+        out.push_str("section .data\n");
+        for (label, content) in &strings {
+            out.push_str(&format!("    {}: db {}, 0\n", label, nasm_bytes(content)));
+        }
+        out.push('\n');
+    }
+    out.push_str("section .text\n");
+    out.push_str("global _start\n\n");
+    out.push_str(&text);
+    out
+}
+
+fn compile_block(
+    instructions: &[Instruction],
+    label_prefix: &str,
+    words: &[CustomCommand],
+    strings: &mut Vec<(String, String)>,
+) -> String {
+    let mut asm = String::new();
+    for (i, ins) in instructions.iter().enumerate() {
+        asm.push_str(&format!("{}_{}:\n", label_prefix, i));
+        match ins.opcode {
+            OpCode::Add => {
+                asm.push_str("    pop rbx\n    pop rax\n    add rax, rbx\n    push rax\n");
+            },
+            OpCode::Sub => {
+                asm.push_str("    pop rbx\n    pop rax\n    sub rax, rbx\n    push rax\n");
+            },
+            OpCode::Mul => {
+                asm.push_str("    pop rbx\n    pop rax\n    imul rax, rbx\n    push rax\n");
+            },
+            OpCode::Div => {
+                asm.push_str("    pop rbx\n    pop rax\n    cqo\n    idiv rbx\n    push rax\n");
+            },
+            OpCode::Dup => {
+                asm.push_str("    pop rax\n    push rax\n    push rax\n");
+            },
+            OpCode::Drop | OpCode::Pop => {
+                asm.push_str("    add rsp, 8\n");
+            },
+            OpCode::Swap => {
+                asm.push_str("    pop rax\n    pop rbx\n    push rax\n    push rbx\n");
+            },
+            OpCode::Over => {
+                asm.push_str("    pop rbx\n    pop rax\n    push rax\n    push rbx\n    push rax\n");
+            },
+            OpCode::Lt => {
+                asm.push_str("    pop rbx\n    pop rax\n    cmp rax, rbx\n    setl al\n    movzx rax, al\n    push rax\n");
+            },
+            OpCode::Gt => {
+                asm.push_str("    pop rbx\n    pop rax\n    cmp rax, rbx\n    setg al\n    movzx rax, al\n    push rax\n");
+            },
+            OpCode::Eq => {
+                asm.push_str("    pop rbx\n    pop rax\n    cmp rax, rbx\n    sete al\n    movzx rax, al\n    push rax\n");
+            },
+            OpCode::Shl => {
+                asm.push_str("    pop rcx\n    pop rax\n    shl rax, cl\n    push rax\n");
+            },
+            OpCode::Shr => {
+                asm.push_str("    pop rcx\n    pop rax\n    shr rax, cl\n    push rax\n");
+            },
+            OpCode::BAnd => {
+                asm.push_str("    pop rbx\n    pop rax\n    and rax, rbx\n    push rax\n");
+            },
+            OpCode::BOr => {
+                asm.push_str("    pop rbx\n    pop rax\n    or rax, rbx\n    push rax\n");
+            },
+            OpCode::Push => {
+                match &ins.values[0] {
+                    Value::Int(n) => asm.push_str(&format!("    push {}\n", n)),
+                    Value::Str(s) => {
+                        let label = format!("str_{}", strings.len());
+                        strings.push((label.clone(), s.clone()));
+                        asm.push_str(&format!("    push {}\n", label));
+                    },
+                }
+            },
+            // Mirrors the interpreter: MEM pushes offset 0 into `mem`, and
+            // Store8/Fetch8 treat the popped address as that offset already.
+            OpCode::Mem => {
+                asm.push_str("    push 0\n");
+            },
+            OpCode::Store8 => {
+                asm.push_str("    pop rbx\n    pop rax\n    mov [mem+rbx], al\n");
+            },
+            OpCode::Fetch8 => {
+                asm.push_str("    pop rbx\n    xor rax, rax\n    mov al, [mem+rbx]\n    push rax\n");
+            },
+            OpCode::Syscall3 => {
+                asm.push_str("    pop rax\n    pop rdx\n    pop rsi\n    pop rdi\n    syscall\n    push rax\n");
+            },
+            OpCode::Call(word_idx) => {
+                asm.push_str(&format!("    call func_{}_{}\n", word_idx, sanitize(&words[word_idx].name)));
+            },
+            OpCode::Jump(target) => {
+                asm.push_str(&format!("    jmp {}_{}\n", label_prefix, target));
+            },
+            OpCode::JumpIfZero(target) => {
+                asm.push_str("    pop rax\n    test rax, rax\n");
+                asm.push_str(&format!("    jz {}_{}\n", label_prefix, target));
+            },
+            OpCode::BeginDefine | OpCode::EndDefine => {},
+            // Debug-printing the stack has no compiled-binary equivalent; PRINT is interpreter-only.
+            OpCode::PrintStack | OpCode::PrintTop => {},
+            OpCode::Exit => {
+                asm.push_str("    mov rax, 60\n    xor rdi, rdi\n    syscall\n");
+            },
+        }
+    }
+    asm.push_str(&format!("{}_{}:\n", label_prefix, instructions.len()));
+    asm
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn nasm_bytes(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\\\""))
+}
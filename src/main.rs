@@ -1,6 +1,8 @@
 use std::io;
 use std::io::Write;
-use std::ops::{Add, Sub, Mul, Div};
+use std::collections::{HashMap, VecDeque};
+
+mod compiler;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum OpCode {
@@ -19,6 +21,20 @@ enum OpCode {
     PrintStack,
     PrintTop,
     Exit,
+    Jump(usize),
+    JumpIfZero(usize),
+    Lt,
+    Gt,
+    Eq,
+    Shl,
+    Shr,
+    BAnd,
+    BOr,
+    Mem,
+    Store8,
+    Fetch8,
+    Syscall3,
+    Call(usize),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +43,9 @@ enum TokenId {
     Minus,
     Star,
     Slash,
+    Lt,
+    Gt,
+    Eq,
     Text,
     Digit,
     Colon,
@@ -37,66 +56,96 @@ enum TokenId {
 enum Value {
     Int(i32),
     Str(String),
-    Ins(Box<Instruction>),
 }
 
-impl Add for Value {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
+impl Value {
+    fn get_int(&self) -> i32 {
+        match self {
+            Value::Int(a) => *a,
+            Value::Str(_) => 0,
+        }
+    }
+    // Arithmetic and comparison ops return a recoverable error instead of panicking
+    // on a type mismatch, division by zero, or over/underflow, so a caller can report
+    // it the same way VirtualMachine::run reports out-of-bounds memory/syscall errors
+    // rather than crashing the whole process.
+    fn add(self, other: Self) -> Result<Self, String> {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Value::Int(this + other),
-            _ => { unimplemented!() },
+            (Value::Int(this), Value::Int(other)) => this.checked_add(other)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("'+' overflowed")),
+            _ => Err(String::from("'+' expects two Int values")),
         }
     }
-}
-
-impl Sub for Value {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
+    fn sub(self, other: Self) -> Result<Self, String> {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Value::Int(this - other),
-            _ => { unimplemented!() },
+            (Value::Int(this), Value::Int(other)) => this.checked_sub(other)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("'-' overflowed")),
+            _ => Err(String::from("'-' expects two Int values")),
         }
     }
-}
-
-impl Mul for Value {
-    type Output = Self;
-
-    fn mul(self, other: Self) -> Self {
+    fn mul(self, other: Self) -> Result<Self, String> {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Value::Int(this * other),
-            _ => { unimplemented!() },
+            (Value::Int(this), Value::Int(other)) => this.checked_mul(other)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("'*' overflowed")),
+            _ => Err(String::from("'*' expects two Int values")),
         }
     }
-}
-
-impl Div for Value {
-    type Output = Self;
-
-    fn div(self, other: Self) -> Self {
+    fn div(self, other: Self) -> Result<Self, String> {
         match (self, other) {
-            (Value::Int(this), Value::Int(other)) => Value::Int(this / other),
-            _ => { unimplemented!() },
+            (Value::Int(_), Value::Int(0)) => Err(String::from("division by zero")),
+            (Value::Int(this), Value::Int(other)) => this.checked_div(other)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("'/' overflowed")),
+            _ => Err(String::from("'/' expects two Int values")),
         }
     }
-}
-
-impl Value {
-    fn get_str(&self) -> String {
-        match self {
-            Value::Int(i) => String::from(i.to_string()),
-            Value::Str(s) => String::from(s),
-            Value::Ins(ins) => String::from(format!("{:?}", ins)),
+    fn lt(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => Ok(Value::Int((this < other) as i32)),
+            _ => Err(String::from("'<' expects two Int values")),
         }
     }
-    fn get_int(&self) -> i32 {
-        match self {
-            Value::Int(a) => *a,
-            Value::Str(_) => 0,
-            Value::Ins(_) => -1,
+    fn gt(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => Ok(Value::Int((this > other) as i32)),
+            _ => Err(String::from("'>' expects two Int values")),
+        }
+    }
+    fn eq(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => Ok(Value::Int((this == other) as i32)),
+            _ => Err(String::from("'=' expects two Int values")),
+        }
+    }
+    fn shl(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => this.checked_shl(other as u32)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("SHL shift amount out of range")),
+            _ => Err(String::from("SHL expects two Int values")),
+        }
+    }
+    fn shr(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => this.checked_shr(other as u32)
+                .map(Value::Int)
+                .ok_or_else(|| String::from("SHR shift amount out of range")),
+            _ => Err(String::from("SHR expects two Int values")),
+        }
+    }
+    fn band(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => Ok(Value::Int(this & other)),
+            _ => Err(String::from("BAND expects two Int values")),
+        }
+    }
+    fn bor(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(this), Value::Int(other)) => Ok(Value::Int(this | other)),
+            _ => Err(String::from("BOR expects two Int values")),
         }
     }
 }
@@ -119,56 +168,342 @@ struct CustomCommand {
     instructions: Vec<Instruction>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Int,
+    Str,
+    Any,
+}
+
+#[derive(Debug)]
+struct TypeError {
+    index: usize,
+    message: String,
+}
+
+// The net stack effect of a word: the types it consumes from (the bottom of)
+// whatever stack its caller hands it, and the types it leaves behind.
+#[derive(Debug, Clone, PartialEq)]
+struct WordEffect {
+    inputs: Vec<Type>,
+    outputs: Vec<Type>,
+}
+
+fn pop_type(stack: &mut Vec<Type>, expected: Type, index: usize) -> Result<Type, TypeError> {
+    match stack.pop() {
+        Some(t) if expected == Type::Any || t == Type::Any || t == expected => Ok(t),
+        Some(t) => Err(TypeError { index, message: format!("expected {:?}, found {:?}", expected, t) }),
+        None => Err(TypeError { index, message: format!("stack underflow, expected {:?}", expected) }),
+    }
+}
+
+// Like pop_type, but an empty stack borrows a slot from whatever the caller
+// will eventually supply instead of failing. Used only while inferring a
+// word's own effect, where there's no real caller stack yet to pop from.
+fn pop_type_or_borrow(stack: &mut Vec<Type>, inputs: &mut Vec<Type>, expected: Type, index: usize) -> Result<Type, TypeError> {
+    if stack.is_empty() {
+        inputs.insert(0, expected);
+        return Ok(expected);
+    }
+    pop_type(stack, expected, index)
+}
+
+// Any unifies with anything (narrowing to the concrete side); two concrete types
+// only unify if they're equal. Used to merge the abstract stacks seen at a
+// control-flow join — e.g. one branch of an IF leaves a DUP'd value untouched
+// (still Any) while the other narrows it to Int, which is not a real conflict.
+fn unify_type(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (Type::Any, t) | (t, Type::Any) => Some(t),
+        (x, y) if x == y => Some(x),
+        _ => None,
+    }
+}
+
+fn unify_stacks(a: &[Type], b: &[Type]) -> Option<Vec<Type>> {
+    if a.len() != b.len() {
+        return None;
+    }
+    a.iter().zip(b.iter()).map(|(x, y)| unify_type(*x, *y)).collect()
+}
+
+// Walks the instruction graph (following Jump/JumpIfZero targets) with a worklist,
+// memoizing the abstract stack (and, in `borrow` mode, the inputs borrowed so far)
+// seen at each instruction index. A mismatch on revisit means two control-flow paths
+// (e.g. the two arms of an IF/ELSE, or a loop body) leave the stack in different
+// shapes, which would otherwise only surface as a runtime panic.
+//
+// `effects` holds the already-inferred effect of every word with a lower index than
+// `self_idx` (a word can only call itself or an earlier word, since codegen resolves
+// OpCode::Call by reserving each word's slot in self.custom_commands at its own
+// definition). A call back to `self_idx` itself (genuine recursion) is treated as
+// stack-neutral: inferring a recursive word's real effect ahead of time would need a
+// fixed-point analysis this checker doesn't attempt, so recursive words are checked
+// under that approximation rather than rejected outright.
+//
+// In `borrow` mode, underflowing pops borrow from `inputs` instead of failing, and the
+// function returns the word's inferred (inputs, outputs). Otherwise, underflow is a
+// hard error and the returned effect's `outputs` is the program's final stack shape.
+fn walk(instructions: &[Instruction], effects: &[WordEffect], self_idx: Option<usize>, borrow: bool) -> Result<WordEffect, TypeError> {
+    let mut visited: HashMap<usize, (Vec<Type>, Vec<Type>)> = HashMap::new();
+    let mut worklist: VecDeque<(usize, Vec<Type>, Vec<Type>)> = VecDeque::new();
+    worklist.push_back((0, vec![], vec![]));
+    let mut end_state: Option<(Vec<Type>, Vec<Type>)> = None;
+
+    while let Some((ip, mut stack, mut inputs)) = worklist.pop_front() {
+        if ip >= instructions.len() {
+            match &end_state {
+                Some((s, i)) if s != &stack || i != &inputs => {
+                    match (unify_stacks(s, &stack), unify_stacks(i, &inputs)) {
+                        (Some(us), Some(ui)) => end_state = Some((us, ui)),
+                        _ => {
+                            return Err(TypeError {
+                                index: instructions.len(),
+                                message: format!("inconsistent stack at end of body: {:?} vs {:?}", s, stack),
+                            });
+                        },
+                    }
+                },
+                _ => end_state = Some((stack, inputs)),
+            }
+            continue;
+        }
+        if let Some((seen_stack, seen_inputs)) = visited.get(&ip) {
+            if seen_stack == &stack && seen_inputs == &inputs {
+                continue;
+            }
+            // Not identical to what we've seen before: unify the two. If they unify to
+            // something narrower (e.g. a prior Any pinned down to Int by this arm),
+            // fall through and reprocess the instruction with the narrowed types so
+            // everything downstream also sees the tighter type.
+            match (unify_stacks(seen_stack, &stack), unify_stacks(seen_inputs, &inputs)) {
+                (Some(us), Some(ui)) => {
+                    stack = us;
+                    inputs = ui;
+                },
+                _ => {
+                    return Err(TypeError {
+                        index: ip,
+                        message: format!("inconsistent stack at instruction {}: {:?} vs {:?}", ip, seen_stack, stack),
+                    });
+                },
+            }
+        }
+        visited.insert(ip, (stack.clone(), inputs.clone()));
+
+        let ins = &instructions[ip];
+        macro_rules! pop {
+            ($expected:expr) => {
+                if borrow { pop_type_or_borrow(&mut stack, &mut inputs, $expected, ip)? } else { pop_type(&mut stack, $expected, ip)? }
+            };
+        }
+        match ins.opcode {
+            OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div |
+            OpCode::Lt | OpCode::Gt | OpCode::Eq |
+            OpCode::Shl | OpCode::Shr | OpCode::BAnd | OpCode::BOr => {
+                pop!(Type::Int);
+                pop!(Type::Int);
+                stack.push(Type::Int);
+            },
+            OpCode::Dup => {
+                let t = pop!(Type::Any);
+                stack.push(t);
+                stack.push(t);
+            },
+            OpCode::Drop | OpCode::Pop => {
+                pop!(Type::Any);
+            },
+            OpCode::Swap => {
+                let b = pop!(Type::Any);
+                let a = pop!(Type::Any);
+                stack.push(b);
+                stack.push(a);
+            },
+            OpCode::Over => {
+                let b = pop!(Type::Any);
+                let a = pop!(Type::Any);
+                stack.push(a);
+                stack.push(b);
+                stack.push(a);
+            },
+            OpCode::Push => {
+                let ty = match &ins.values[0] {
+                    Value::Int(_) => Type::Int,
+                    Value::Str(_) => Type::Str,
+                };
+                stack.push(ty);
+            },
+            OpCode::Call(word_idx) => {
+                if Some(word_idx) == self_idx {
+                    // Genuine recursion: approximated as stack-neutral, see above.
+                } else {
+                    let effect = &effects[word_idx];
+                    for t in effect.inputs.iter().rev() {
+                        pop!(*t);
+                    }
+                    for t in &effect.outputs {
+                        stack.push(*t);
+                    }
+                }
+            },
+            OpCode::Mem => {
+                stack.push(Type::Int);
+            },
+            OpCode::Store8 => {
+                pop!(Type::Int);
+                pop!(Type::Int);
+            },
+            OpCode::Fetch8 => {
+                pop!(Type::Int);
+                stack.push(Type::Int);
+            },
+            OpCode::Syscall3 => {
+                pop!(Type::Int);
+                pop!(Type::Int);
+                pop!(Type::Int);
+                pop!(Type::Int);
+                stack.push(Type::Int);
+            },
+            OpCode::BeginDefine | OpCode::EndDefine |
+            OpCode::PrintStack | OpCode::PrintTop | OpCode::Exit => {},
+            OpCode::Jump(target) => {
+                worklist.push_back((target, stack, inputs));
+                continue;
+            },
+            OpCode::JumpIfZero(target) => {
+                pop!(Type::Int);
+                worklist.push_back((ip+1, stack.clone(), inputs.clone()));
+                worklist.push_back((target, stack, inputs));
+                continue;
+            },
+        }
+        worklist.push_back((ip+1, stack, inputs));
+    }
+
+    let (outputs, inputs) = end_state.unwrap_or_default();
+    Ok(WordEffect { inputs, outputs })
+}
+
+fn typecheck(instructions: &[Instruction], effects: &[WordEffect]) -> Result<(), TypeError> {
+    walk(instructions, effects, None, false)?;
+    Ok(())
+}
+
+// Infers word `word_idx`'s stack effect by walking its own body, typechecking it in
+// the process. `effects` must already hold an entry for every word defined before it.
+fn infer_word_effect(word_idx: usize, commands: &[CustomCommand], effects: &[WordEffect]) -> Result<WordEffect, TypeError> {
+    walk(&commands[word_idx].instructions, effects, Some(word_idx), true)
+}
+
+// Computes every word's stack effect in definition order, so that by the time a word
+// is analyzed, `effects` already holds the effect of every (non-recursive) word it
+// can call. This is also where word bodies get typechecked; `typecheck` itself only
+// walks the top-level instruction stream.
+fn compute_word_effects(commands: &[CustomCommand]) -> Result<Vec<WordEffect>, TypeError> {
+    let mut effects = Vec::with_capacity(commands.len());
+    for word_idx in 0..commands.len() {
+        effects.push(infer_word_effect(word_idx, commands, &effects)?);
+    }
+    Ok(effects)
+}
+
+const MEM_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, Clone)]
 struct VirtualMachine {
     stack: Vec<Value>,
     custom_commands: Vec<CustomCommand>,
     ip: usize,
+    mem: Vec<u8>,
 }
 
-impl<'a> VirtualMachine {
+impl VirtualMachine {
     fn new() -> Self {
-        Self { stack: vec![], custom_commands: vec![], ip: 0 }
+        Self { stack: vec![], custom_commands: vec![], ip: 0, mem: vec![0; MEM_SIZE] }
     }
 
     fn stack(&self) -> &Vec<Value> {
         &self.stack
     }
 
-    fn execute(&'a mut self, command: &'a str) -> bool {
-        self.run(&self.codegen(&self.parse(command)))
+    fn execute(&mut self, command: &str) -> bool {
+        let tokens = match self.parse(command) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("parse error: {}", e);
+                return false;
+            },
+        };
+        let instructions = match self.codegen(&tokens) {
+            Ok(instructions) => instructions,
+            Err(e) => {
+                eprintln!("parse error: {}", e);
+                return false;
+            },
+        };
+        self.run(&instructions)
+    }
+
+    // Reads a whole .rorth file and runs it as a single program, the way `execute`
+    // runs a single REPL line. Case for keywords is folded during tokenizing (see
+    // `parse_tokens`), so the file is read and handed to `execute` as-is — a raw
+    // uppercase pass here would also mangle INCLUDE paths and string literals.
+    fn run_file(&mut self, path: &str) -> io::Result<bool> {
+        let source = std::fs::read_to_string(path)?;
+        Ok(self.execute(&source))
     }
 
     fn run(&mut self, instructions: &Vec<Instruction>) -> bool {
+        let effects = match compute_word_effects(&self.custom_commands) {
+            Ok(effects) => effects,
+            Err(e) => {
+                eprintln!("type error at instruction {}: {}", e.index, e.message);
+                return false;
+            },
+        };
+        if let Err(e) = typecheck(instructions, &effects) {
+            eprintln!("type error at instruction {}: {}", e.index, e.message);
+            return false;
+        }
         self.ip = 0;
+        let mut active = instructions.clone();
+        let mut call_stack: Vec<(Vec<Instruction>, usize)> = vec![];
         let mut should_exit = false;
         loop {
-            if self.ip < instructions.len() {
-                let ins = instructions[self.ip].clone();
+            if self.ip < active.len() {
+                let ins = active[self.ip].clone();
                 match ins.opcode {
                     OpCode::Add => {
                         let b = self.stack.pop().unwrap();
                         let a = self.stack.pop().unwrap();
-                        self.stack.push(a+b);
-                        self.ip += 1;
+                        match a.add(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
                     },
                     OpCode::Sub => {
                         let b = self.stack.pop().unwrap();
                         let a = self.stack.pop().unwrap();
-                        self.stack.push(a-b);
-                        self.ip += 1;
+                        match a.sub(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
                     },
                     OpCode::Mul => {
                         let b = self.stack.pop().unwrap();
                         let a = self.stack.pop().unwrap();
-                        self.stack.push(a*b);
-                        self.ip += 1;
+                        match a.mul(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
                     },
                     OpCode::Div => {
                         let b = self.stack.pop().unwrap();
                         let a = self.stack.pop().unwrap();
-                        self.stack.push(a/b);
-                        self.ip += 1;
+                        match a.div(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
                     },
                     OpCode::Dup => {
                         let a = self.stack.pop().unwrap();
@@ -195,41 +530,22 @@ impl<'a> VirtualMachine {
                         self.stack.push(a);
                         self.ip += 1;
                     },
-                    OpCode::BeginDefine => {
-                        let name = ins.values[0].clone();
+                    // Words are registered into self.custom_commands by codegen (so that
+                    // OpCode::Call can be resolved at compile time); BeginDefine is only
+                    // a marker left in the stream and has no effect at run time.
+                    OpCode::BeginDefine | OpCode::EndDefine => {
                         self.ip += 1;
-                        let mut cmd = CustomCommand {
-                            name: name.get_str(),
-                            instructions: vec![],
-                        };
-                        for i in 1..ins.values.len() {
-                            let ii = ins.values[i].clone();
-                            match ii {
-                                //FIXME: not working, the instructions are being pushed as Str
-                                //       need to se if Str is referring to an instruction...
-                                Value::Ins(iii) => cmd.instructions.push(*iii),
-                                _ => panic!("Expected an Instruction"),
-                            }
-                        }
-                        self.custom_commands.push(cmd); 
-                        self.ip += ins.values.len()-1;
                     },
-                    //TODO: this needs to check if the to be pushed values
-                    //      are not custom words, if so, execute them
                     OpCode::Push => {
                         let val = ins.values[0].clone();
-                        let mut is_cmd = false;
-                        for cmd in self.custom_commands.iter() {
-                            if cmd.name == val.get_str() {
-                                is_cmd = true;
-                                self.clone().run(&cmd.instructions);
-                            }
-                        }
-                        if !is_cmd {
-                            self.stack.push(val);
-                        }
+                        self.stack.push(val);
                         self.ip += 1;
                     },
+                    OpCode::Call(word_idx) => {
+                        call_stack.push((active.clone(), self.ip + 1));
+                        active = self.custom_commands[word_idx].instructions.clone();
+                        self.ip = 0;
+                    },
                     OpCode::Pop => {
                         let _ = self.stack.pop();
                         self.ip += 1;
@@ -256,10 +572,137 @@ impl<'a> VirtualMachine {
                         should_exit = true;
                         self.ip += 1;
                     },
-                    _ => {
-                        unimplemented!();
-                    }
+                    OpCode::Jump(target) => {
+                        self.ip = target;
+                    },
+                    OpCode::JumpIfZero(target) => {
+                        let cond = self.stack.pop().unwrap();
+                        if cond.get_int() == 0 {
+                            self.ip = target;
+                        } else {
+                            self.ip += 1;
+                        }
+                    },
+                    OpCode::Lt => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.lt(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::Gt => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.gt(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::Eq => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.eq(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::Shl => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.shl(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::Shr => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.shr(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::BAnd => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.band(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::BOr => {
+                        let b = self.stack.pop().unwrap();
+                        let a = self.stack.pop().unwrap();
+                        match a.bor(b) {
+                            Ok(v) => { self.stack.push(v); self.ip += 1; },
+                            Err(msg) => { eprintln!("runtime error: {}", msg); should_exit = true; break; },
+                        }
+                    },
+                    OpCode::Mem => {
+                        self.stack.push(Value::Int(0));
+                        self.ip += 1;
+                    },
+                    OpCode::Store8 => {
+                        let addr = self.stack.pop().unwrap().get_int() as usize;
+                        let val = self.stack.pop().unwrap().get_int() as u8;
+                        if addr >= self.mem.len() {
+                            eprintln!("runtime error: STORE8 address {} out of bounds (mem size {})", addr, self.mem.len());
+                            should_exit = true;
+                            break;
+                        }
+                        self.mem[addr] = val;
+                        self.ip += 1;
+                    },
+                    OpCode::Fetch8 => {
+                        let addr = self.stack.pop().unwrap().get_int() as usize;
+                        if addr >= self.mem.len() {
+                            eprintln!("runtime error: FETCH8 address {} out of bounds (mem size {})", addr, self.mem.len());
+                            should_exit = true;
+                            break;
+                        }
+                        self.stack.push(Value::Int(self.mem[addr] as i32));
+                        self.ip += 1;
+                    },
+                    OpCode::Syscall3 => {
+                        let number = self.stack.pop().unwrap().get_int();
+                        let arg3 = self.stack.pop().unwrap().get_int();
+                        let arg2 = self.stack.pop().unwrap().get_int();
+                        let arg1 = self.stack.pop().unwrap().get_int();
+                        match number {
+                            1 => {
+                                let fd = arg1;
+                                let addr = arg2 as usize;
+                                let count = arg3 as usize;
+                                if addr.checked_add(count).is_none_or(|end| end > self.mem.len()) {
+                                    eprintln!("runtime error: SYSCALL3 write out of bounds (addr {}, count {}, mem size {})", addr, count, self.mem.len());
+                                    should_exit = true;
+                                    break;
+                                }
+                                let bytes = &self.mem[addr..addr+count];
+                                let written = match fd {
+                                    1 => io::stdout().write(bytes),
+                                    2 => io::stderr().write(bytes),
+                                    _ => {
+                                        eprintln!("runtime error: unsupported file descriptor {} for SYSCALL3 write", fd);
+                                        should_exit = true;
+                                        break;
+                                    },
+                                };
+                                self.stack.push(Value::Int(written.unwrap_or(0) as i32));
+                            },
+                            _ => {
+                                eprintln!("runtime error: unsupported syscall number {}", number);
+                                should_exit = true;
+                                break;
+                            },
+                        }
+                        self.ip += 1;
+                    },
                 }
+            } else if let Some((ret_active, ret_ip)) = call_stack.pop() {
+                active = ret_active;
+                self.ip = ret_ip;
             } else {
                 break;
             }
@@ -267,25 +710,38 @@ impl<'a> VirtualMachine {
         should_exit
     }
 
-    fn codegen(&self, tokens: &[Token]) -> Vec<Instruction> {
+    fn codegen(&mut self, tokens: &[Token]) -> Result<Vec<Instruction>, String> {
         let mut opcodes: Vec<Instruction> = vec![];
+        let mut body: Vec<Instruction> = vec![];
         let mut declaration_mode = false;
         let mut definition_mode = false;
+        let mut cf_stack: Vec<usize> = vec![];
         for tok in tokens.iter() {
+            // While compiling a word's body, instructions are appended to `body`
+            // instead of the top-level `opcodes`. The word's slot in
+            // self.custom_commands is reserved up front (see the BeginDefine arm
+            // below), so a call to itself inside its own body already resolves to
+            // OpCode::Call; SEMICOLON just fills in the reserved slot's body.
+            let target: &mut Vec<Instruction> = if definition_mode { &mut body } else { &mut opcodes };
             match tok.id {
-                TokenId::Plus =>  { opcodes.push(Instruction { opcode: OpCode::Add, values: vec![] }); }
-                TokenId::Minus => { opcodes.push(Instruction { opcode: OpCode::Sub, values: vec![] }); }
-                TokenId::Star =>  { opcodes.push(Instruction { opcode: OpCode::Mul, values: vec![] }); }
-                TokenId::Slash => { opcodes.push(Instruction { opcode: OpCode::Div, values: vec![] }); }
+                TokenId::Plus =>  { target.push(Instruction { opcode: OpCode::Add, values: vec![] }); }
+                TokenId::Minus => { target.push(Instruction { opcode: OpCode::Sub, values: vec![] }); }
+                TokenId::Star =>  { target.push(Instruction { opcode: OpCode::Mul, values: vec![] }); }
+                TokenId::Slash => { target.push(Instruction { opcode: OpCode::Div, values: vec![] }); }
+                TokenId::Lt => { target.push(Instruction { opcode: OpCode::Lt, values: vec![] }); }
+                TokenId::Gt => { target.push(Instruction { opcode: OpCode::Gt, values: vec![] }); }
+                TokenId::Eq => { target.push(Instruction { opcode: OpCode::Eq, values: vec![] }); }
                 TokenId::Colon => {
                     declaration_mode = true;
                 }
                 TokenId::Semicolon => {
                     definition_mode = false;
+                    let word_idx = self.custom_commands.len() - 1;
+                    self.custom_commands[word_idx].instructions = body.drain(..).collect();
                     opcodes.push(Instruction { opcode: OpCode::EndDefine, values: vec![] });
                 }
                 TokenId::Digit => {
-                    opcodes.push(Instruction {
+                    target.push(Instruction {
                         opcode: OpCode::Push,
                         values: vec![Value::Int(tok.itself.clone().unwrap().parse::<i32>().unwrap())],
                     });
@@ -293,51 +749,129 @@ impl<'a> VirtualMachine {
                 TokenId::Text => {
                     match &tok.itself {
                         Some(a) => match a.as_str() {
-                            "DUP" =>   { opcodes.push(Instruction { opcode: OpCode::Dup, values: vec![] }) },
-                            "DROP" =>  { opcodes.push(Instruction { opcode: OpCode::Drop, values: vec![] }) },
-                            "SWAP" =>  { opcodes.push(Instruction { opcode: OpCode::Swap, values: vec![] }) },
-                            "OVER" =>  { opcodes.push(Instruction { opcode: OpCode::Over, values: vec![] }) },
-                            "PRINT" => { opcodes.push(Instruction { opcode: OpCode::PrintStack, values: vec![] }) },
-                            "POP" =>   { opcodes.push(Instruction { opcode: OpCode::Pop, values: vec![] }) },
-                            "EXIT" =>  { opcodes.push(Instruction { opcode: OpCode::Exit, values: vec![] }) },
+                            "DUP" =>   { target.push(Instruction { opcode: OpCode::Dup, values: vec![] }) },
+                            "DROP" =>  { target.push(Instruction { opcode: OpCode::Drop, values: vec![] }) },
+                            "SWAP" =>  { target.push(Instruction { opcode: OpCode::Swap, values: vec![] }) },
+                            "OVER" =>  { target.push(Instruction { opcode: OpCode::Over, values: vec![] }) },
+                            "PRINT" => { target.push(Instruction { opcode: OpCode::PrintStack, values: vec![] }) },
+                            "POP" =>   { target.push(Instruction { opcode: OpCode::Pop, values: vec![] }) },
+                            "EXIT" =>  { target.push(Instruction { opcode: OpCode::Exit, values: vec![] }) },
+                            "IF" => {
+                                target.push(Instruction { opcode: OpCode::JumpIfZero(usize::MAX), values: vec![] });
+                                cf_stack.push(target.len()-1);
+                            },
+                            "ELSE" => {
+                                target.push(Instruction { opcode: OpCode::Jump(usize::MAX), values: vec![] });
+                                let if_idx = cf_stack.pop().ok_or_else(|| String::from("ELSE without matching IF"))?;
+                                target[if_idx].opcode = OpCode::JumpIfZero(target.len());
+                                cf_stack.push(target.len()-1);
+                            },
+                            "THEN" => {
+                                let idx = cf_stack.pop().ok_or_else(|| String::from("THEN without matching IF/ELSE"))?;
+                                match target[idx].opcode {
+                                    OpCode::JumpIfZero(_) => target[idx].opcode = OpCode::JumpIfZero(target.len()),
+                                    OpCode::Jump(_) => target[idx].opcode = OpCode::Jump(target.len()),
+                                    _ => return Err(String::from("THEN patching a non-branch instruction")),
+                                }
+                            },
+                            "BEGIN" => {
+                                cf_stack.push(target.len());
+                            },
+                            "WHILE" => {
+                                target.push(Instruction { opcode: OpCode::JumpIfZero(usize::MAX), values: vec![] });
+                                cf_stack.push(target.len()-1);
+                            },
+                            "REPEAT" => {
+                                let while_idx = cf_stack.pop().ok_or_else(|| String::from("REPEAT without matching WHILE"))?;
+                                let begin_idx = cf_stack.pop().ok_or_else(|| String::from("REPEAT without matching BEGIN"))?;
+                                target.push(Instruction { opcode: OpCode::Jump(begin_idx), values: vec![] });
+                                target[while_idx].opcode = OpCode::JumpIfZero(target.len());
+                            },
+                            "SHL" =>  { target.push(Instruction { opcode: OpCode::Shl, values: vec![] }) },
+                            "SHR" =>  { target.push(Instruction { opcode: OpCode::Shr, values: vec![] }) },
+                            "BAND" => { target.push(Instruction { opcode: OpCode::BAnd, values: vec![] }) },
+                            "BOR" =>  { target.push(Instruction { opcode: OpCode::BOr, values: vec![] }) },
+                            "MEM" =>      { target.push(Instruction { opcode: OpCode::Mem, values: vec![] }) },
+                            "!8" =>       { target.push(Instruction { opcode: OpCode::Store8, values: vec![] }) },
+                            "@8" =>       { target.push(Instruction { opcode: OpCode::Fetch8, values: vec![] }) },
+                            "SYSCALL3" => { target.push(Instruction { opcode: OpCode::Syscall3, values: vec![] }) },
                             itself => {
                                 if declaration_mode {
                                     opcodes.push(Instruction {
                                         opcode: OpCode::BeginDefine,
                                         values: vec![Value::Str(String::from(itself))],
                                     });
+                                    // Reserve the word's slot now (body filled in at
+                                    // SEMICOLON) so a call to itself inside its own
+                                    // body can already resolve to OpCode::Call.
+                                    self.custom_commands.push(CustomCommand {
+                                        name: String::from(itself),
+                                        instructions: vec![],
+                                    });
                                     declaration_mode = false;
                                     definition_mode = true;
-                                } else if definition_mode {
-                                    println!("=== def mode ===");
-                                    let idx = opcodes.len()-1;
-                                    opcodes[idx].values.push(Value::Str(String::from(itself)));
                                 } else {
-                                    opcodes.push(Instruction {
-                                        opcode: OpCode::Push,
-                                        values: vec![Value::Str(String::from(itself))],
-                                    });
+                                    match self.custom_commands.iter().position(|cmd| cmd.name == itself) {
+                                        Some(word_idx) => target.push(Instruction { opcode: OpCode::Call(word_idx), values: vec![] }),
+                                        None => target.push(Instruction {
+                                            opcode: OpCode::Push,
+                                            values: vec![Value::Str(String::from(itself))],
+                                        }),
+                                    }
                                 }
                             },
                         },
                         None => {},
-                    } 
+                    }
                 }
             }
         }
+        if !cf_stack.is_empty() {
+            return Err(String::from("unbalanced control flow: IF/BEGIN/WHILE without a matching THEN/REPEAT"));
+        }
         if opcodes.len() > 0 && opcodes[opcodes.len()-1].opcode != OpCode::PrintStack {
             opcodes.push(Instruction { opcode: OpCode::PrintTop, values: vec![] });
         }
-        opcodes
+        Ok(opcodes)
     }
 
-    fn parse(&'a self, command: &'a str) -> Vec<Token> {
+    fn parse(&self, command: &str) -> Result<Vec<Token>, String> {
+        self.parse_tokens(command, &mut vec![])
+    }
+
+    // Recursive worker behind `parse`. `including` holds the canonicalized path
+    // of every file currently being spliced in, so an INCLUDE chain that loops
+    // back on a file already in progress is rejected instead of recursing forever.
+    fn parse_tokens(&self, command: &str, including: &mut Vec<std::path::PathBuf>) -> Result<Vec<Token>, String> {
         let mut tokens = vec![];
         let words = command.split_ascii_whitespace().collect::<Vec<&str>>();
+        let mut skip_until = 0usize;
 
         for i in 0..words.len() {
+            if i < skip_until {
+                continue;
+            }
             let word = words[i];
 
+            if word.eq_ignore_ascii_case("INCLUDE") {
+                let path = words.get(i+1)
+                    .ok_or_else(|| String::from("INCLUDE without a path"))?
+                    .trim_matches('"');
+                let canonical = std::fs::canonicalize(path)
+                    .map_err(|e| format!("INCLUDE failed to resolve {}: {}", path, e))?;
+                if including.contains(&canonical) {
+                    return Err(format!("INCLUDE cycle detected: {}", canonical.display()));
+                }
+                let source = std::fs::read_to_string(&canonical)
+                    .map_err(|e| format!("INCLUDE failed to read {}: {}", path, e))?;
+                including.push(canonical);
+                let included = self.parse_tokens(&source, including);
+                including.pop();
+                tokens.extend(included?);
+                skip_until = i + 2;
+                continue;
+            }
+
             if word.starts_with("\"") {
                 if word.ends_with("\"") {
                     tokens.push(Token { id: TokenId::Text, itself: Some(String::from(word)) } );
@@ -365,14 +899,27 @@ impl<'a> VirtualMachine {
             match word.parse::<i32>() {
                 Ok(_) => tokens.push(Token { id : TokenId::Digit, itself: Some(String::from(word)) }),
                 Err(_) => {
-                    match word {
+                    // Keywords are matched case-insensitively (the source is no longer
+                    // folded to uppercase as a whole, so REPL/file input stays usable in
+                    // any case); the stored token text is always the canonical uppercase
+                    // form codegen matches against. Anything that isn't a keyword is a
+                    // custom word name, kept verbatim so definitions and calls still have
+                    // to agree on case.
+                    let upper = word.to_uppercase();
+                    match upper.as_str() {
                         "+" => tokens.push(Token { id: TokenId::Plus, itself: None } ),
                         "-" => tokens.push(Token { id: TokenId::Minus, itself: None } ),
                         "*" => tokens.push(Token { id: TokenId::Star, itself: None } ),
                         "/" => tokens.push(Token { id: TokenId::Slash, itself: None } ),
+                        "<" => tokens.push(Token { id: TokenId::Lt, itself: None } ),
+                        ">" => tokens.push(Token { id: TokenId::Gt, itself: None } ),
+                        "=" => tokens.push(Token { id: TokenId::Eq, itself: None } ),
                         ":" => tokens.push(Token { id: TokenId::Colon, itself: None } ),
                         ";" => tokens.push(Token { id: TokenId::Semicolon, itself: None } ),
-                        "DUP"|"DROP"|"SWAP"|"OVER"|"PRINT"|"POP"|"EXIT" => tokens.push(Token { id: TokenId::Text, itself: Some(String::from(word)) } ),
+                        "DUP"|"DROP"|"SWAP"|"OVER"|"PRINT"|"POP"|"EXIT"|
+                        "IF"|"ELSE"|"THEN"|"BEGIN"|"WHILE"|"REPEAT"|
+                        "SHL"|"SHR"|"BAND"|"BOR"|
+                        "MEM"|"!8"|"@8"|"SYSCALL3" => tokens.push(Token { id: TokenId::Text, itself: Some(upper) } ),
                         _ => {
                             if word.starts_with("\"") && word.ends_with("\"") {
                                 tokens.push(Token { id: TokenId::Text, itself: Some(String::from(&word[1..word.len()-1])) } );
@@ -384,19 +931,94 @@ impl<'a> VirtualMachine {
                 }
             }
         }
-        tokens
+        Ok(tokens)
+    }
+}
+
+fn compile_to_binary(src_path: &str) -> io::Result<()> {
+    let source = std::fs::read_to_string(src_path)?;
+    let mut vm = VirtualMachine::new();
+    let tokens = match vm.parse(&source) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            eprintln!("parse error: {}", e);
+            return Ok(());
+        },
+    };
+    let instructions = match vm.codegen(&tokens) {
+        Ok(instructions) => instructions,
+        Err(e) => {
+            eprintln!("parse error: {}", e);
+            return Ok(());
+        },
+    };
+    let effects = match compute_word_effects(&vm.custom_commands) {
+        Ok(effects) => effects,
+        Err(e) => {
+            eprintln!("type error at instruction {}: {}", e.index, e.message);
+            return Ok(());
+        },
+    };
+    if let Err(e) = typecheck(&instructions, &effects) {
+        eprintln!("type error at instruction {}: {}", e.index, e.message);
+        return Ok(());
+    }
+
+    let asm = compiler::compile_to_nasm(&instructions, &vm.custom_commands);
+    let stem = std::path::Path::new(src_path).with_extension("");
+    let asm_path = stem.with_extension("asm");
+    let obj_path = stem.with_extension("o");
+    std::fs::write(&asm_path, asm)?;
+
+    let nasm_status = match std::process::Command::new("nasm")
+        .args(["-f", "elf64", asm_path.to_str().unwrap(), "-o", obj_path.to_str().unwrap()])
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("nasm not found (is it installed?): {}", e);
+            return Ok(());
+        },
+    };
+    if !nasm_status.success() {
+        eprintln!("nasm failed to assemble {}", asm_path.display());
+        return Ok(());
+    }
+
+    let ld_status = match std::process::Command::new("ld")
+        .args([obj_path.to_str().unwrap(), "-o", stem.to_str().unwrap()])
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("ld not found (is it installed?): {}", e);
+            return Ok(());
+        },
+    };
+    if !ld_status.success() {
+        eprintln!("ld failed to link {}", obj_path.display());
     }
+
+    Ok(())
 }
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() >= 3 && args[1] == "--compile" {
+        return compile_to_binary(&args[2]);
+    }
 
-    let mut buffer = String::new();
     let mut vm = VirtualMachine::new();
+    if args.len() >= 2 {
+        vm.run_file(&args[1])?;
+        return Ok(());
+    }
+
+    let mut buffer = String::new();
     loop {
         print!("> ");
         let _ = io::stdout().flush();
         io::stdin().read_line(&mut buffer)?;
-        buffer = buffer.to_uppercase();
         if vm.execute(&buffer) {
             break;
         }
@@ -405,3 +1027,47 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_word_computes_its_result() {
+        let mut vm = VirtualMachine::new();
+        vm.execute(": SQUARE DUP * ;");
+        vm.execute("5 SQUARE");
+        assert_eq!(vm.stack().last(), Some(&Value::Int(25)));
+    }
+
+    #[test]
+    fn begin_while_repeat_loop_is_stack_neutral() {
+        let mut vm = VirtualMachine::new();
+        vm.execute("5 BEGIN DUP 0 > WHILE 1 - REPEAT");
+        assert_eq!(vm.stack(), &vec![Value::Int(0)]);
+    }
+
+    #[test]
+    fn typecheck_rejects_branches_with_different_stack_shapes() {
+        let mut vm = VirtualMachine::new();
+        let tokens = vm.parse("0 IF 1 ELSE THEN").unwrap();
+        let instructions = vm.codegen(&tokens).unwrap();
+        let effects = compute_word_effects(&vm.custom_commands).unwrap();
+        assert!(typecheck(&instructions, &effects).is_err());
+    }
+
+    #[test]
+    fn include_splices_tokens_from_another_file() {
+        let dir = std::env::temp_dir().join(format!("rorth_include_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lib.rorth"), ": DOUBLE DUP + ;").unwrap();
+        let main_path = dir.join("main.rorth");
+        std::fs::write(&main_path, format!("INCLUDE \"{}\"\n3 DOUBLE", dir.join("lib.rorth").display())).unwrap();
+
+        let mut vm = VirtualMachine::new();
+        vm.run_file(main_path.to_str().unwrap()).unwrap();
+
+        assert_eq!(vm.stack().last(), Some(&Value::Int(6)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}